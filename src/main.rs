@@ -7,36 +7,148 @@
 /// both parties connect TO this relay server (outbound connections only),
 /// eliminating the need for firewall configuration.
 
+mod auth;
+mod codec;
+mod metrics;
 mod protocol;
+mod room;
 
 use anyhow::{Context, Result};
-use protocol::{Message, MessageFramer};
+use bytes::BytesMut;
+use codec::RelayCodec;
+use futures::{SinkExt, StreamExt};
+use metrics::Metrics;
+use protocol::Message;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{mpsc, Mutex};
-use tokio::time::{timeout, Duration};
+use tokio::time::{timeout, Duration, Instant};
+use tokio_util::codec::{Framed, FramedParts};
 use tracing::{error, info, warn};
 
 /// Relay server port (configurable via command line)
 const DEFAULT_RELAY_PORT: u16 = 8444;
 
+/// Metrics/healthz HTTP port (configurable via command line)
+const DEFAULT_METRICS_PORT: u16 = 9444;
+
 /// Maximum time to wait for relay pairing
 const PAIRING_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Longest reservation a host may request, regardless of the ttl it asks for
+const MAX_RESERVATION_TTL: Duration = Duration::from_secs(3600);
+
+/// Default cumulative byte cap enforced on a circuit (both directions combined)
+const DEFAULT_MAX_CIRCUIT_BYTES: u64 = 10 * 1024 * 1024 * 1024; // 10GB
+
+/// Default lifetime cap enforced on a circuit once its two halves are paired
+const DEFAULT_MAX_CIRCUIT_DURATION: Duration = Duration::from_secs(3600);
+
+/// How often the keepalive proxy checks for idle sides and due pings
+const KEEPALIVE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a side may go without activity before the relay pings it
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a side may go without activity (including an answered ping)
+/// before the relay gives up and tears the circuit down
+const IDLE_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// How long to let in-flight circuits keep running after a shutdown signal
+/// before giving up on a clean drain and exiting anyway
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often `main` polls for the drain to finish
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Broadcasts whether the relay has started shutting down, so in-flight
+/// handlers can stop taking on new work and unblock any pending waits
+type ShutdownSignal = tokio::sync::watch::Receiver<bool>;
+
+/// Resolves once a shutdown signal (Ctrl+C or SIGTERM) is received
+async fn wait_for_shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Whether the relay has started draining and should stop accepting new work
+fn is_draining(shutdown: &ShutdownSignal) -> bool {
+    *shutdown.borrow()
+}
+
 /// Connection half - represents one side of a relay connection
-struct ConnectionHalf {
-    stream: TcpStream,
-    peer_addr: SocketAddr,
-    role: String,
-    peer_id: String,
+pub(crate) struct ConnectionHalf {
+    pub(crate) stream: TcpStream,
+    pub(crate) peer_addr: SocketAddr,
+    pub(crate) role: String,
+    pub(crate) peer_id: String,
+    /// Whether this peer opted into Ping/Pong keepalive framing
+    pub(crate) keepalive: bool,
+    /// Bytes the codec had already buffered past the handshake frame
+    pub(crate) leftover: BytesMut,
 }
 
 /// Relay pairing state
 type RelayPairings = Arc<Mutex<HashMap<String, mpsc::Sender<ConnectionHalf>>>>;
 
+/// Caps enforced on a single proxied circuit
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limits {
+    pub(crate) max_bytes: u64,
+    pub(crate) max_duration: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_CIRCUIT_BYTES,
+            max_duration: DEFAULT_MAX_CIRCUIT_DURATION,
+        }
+    }
+}
+
+/// A UUID slot claimed by a host ahead of time, awaiting a client to dial in
+struct Reservation {
+    tx: mpsc::Sender<ConnectionHalf>,
+    expiry: Instant,
+}
+
+/// Standing reservations, keyed by UUID
+type Reservations = Arc<Mutex<HashMap<String, Reservation>>>;
+
+/// Server-wide state shared across every connection handler; each field is
+/// itself an `Arc` (or cheaply `Clone`), so cloning `RelayState` is cheap.
+#[derive(Clone)]
+struct RelayState {
+    pairings: RelayPairings,
+    reservations: Reservations,
+    rooms: room::Rooms,
+    metrics: Arc<Metrics>,
+    network_key: Arc<Option<Vec<u8>>>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -52,8 +164,24 @@ async fn main() -> Result<()> {
     } else {
         DEFAULT_RELAY_PORT
     };
+    let metrics_port = if args.len() > 2 {
+        args[2].parse().unwrap_or(DEFAULT_METRICS_PORT)
+    } else {
+        DEFAULT_METRICS_PORT
+    };
+    let network_key: Arc<Option<Vec<u8>>> = Arc::new(
+        args.get(3)
+            .cloned()
+            .or_else(|| std::env::var("RELAY_NETWORK_KEY").ok())
+            .map(|k| k.into_bytes()),
+    );
 
     info!("🚀 Starting Remotely Relay Server on port {}", port);
+    if network_key.is_some() {
+        info!("🔐 Pre-shared network-key authentication enabled");
+    } else {
+        warn!("🔓 No network key configured - relay will pair anyone who can reach it");
+    }
 
     // Bind to all interfaces
     let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
@@ -65,70 +193,354 @@ async fn main() -> Result<()> {
 
     // Shared state: UUID -> channel to send paired connection
     let pairings: RelayPairings = Arc::new(Mutex::new(HashMap::new()));
+    // Shared state: UUID -> standing reservation awaiting a client
+    let reservations: Reservations = Arc::new(Mutex::new(HashMap::new()));
+    // Shared state: UUID -> N-party broadcast room
+    let rooms: room::Rooms = Arc::new(Mutex::new(HashMap::new()));
+    let metrics = Arc::new(Metrics::new());
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
+    spawn_reservation_sweeper(Arc::clone(&reservations));
+    metrics::spawn_metrics_server(Arc::clone(&metrics), metrics_port);
+
+    let state = RelayState {
+        pairings,
+        reservations,
+        rooms,
+        metrics: Arc::clone(&metrics),
+        network_key,
+    };
+
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        info!("🛑 Shutdown signal received, draining in-flight circuits...");
+        let _ = shutdown_tx.send(true);
+    });
+
+    let mut accept_shutdown = shutdown_rx.clone();
     loop {
-        match listener.accept().await {
-            Ok((stream, addr)) => {
-                info!("New connection from {}", addr);
-                let pairings = Arc::clone(&pairings);
-                tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream, addr, pairings).await {
-                        error!("Connection handler error from {}: {}", addr, e);
-                    }
-                });
+        tokio::select! {
+            _ = accept_shutdown.changed() => {
+                info!("🚪 No longer accepting new connections");
+                break;
             }
-            Err(e) => {
-                error!("Failed to accept connection: {}", e);
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((stream, addr)) => {
+                        info!("New connection from {}", addr);
+                        metrics.connection_accepted();
+                        let state = state.clone();
+                        let shutdown = shutdown_rx.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, addr, state, shutdown).await
+                            {
+                                error!("Connection handler error from {}: {}", addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                }
             }
         }
     }
+
+    info!(
+        "⏳ Draining up to {} active circuit(s) (deadline {}s)...",
+        metrics.active_circuits(),
+        DRAIN_TIMEOUT.as_secs()
+    );
+    let drain_deadline = Instant::now() + DRAIN_TIMEOUT;
+    while metrics.active_circuits() > 0 && Instant::now() < drain_deadline {
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+    if metrics.active_circuits() > 0 {
+        warn!(
+            "Drain deadline reached with {} circuit(s) still active, exiting anyway",
+            metrics.active_circuits()
+        );
+    } else {
+        info!("✓ All circuits drained, shutting down cleanly");
+    }
+
+    Ok(())
+}
+
+/// Periodically drop reservations whose TTL has elapsed without being claimed
+fn spawn_reservation_sweeper(reservations: Reservations) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            let mut guard = reservations.lock().await;
+            let before = guard.len();
+            guard.retain(|_, reservation| reservation.expiry > now);
+            let removed = before - guard.len();
+            if removed > 0 {
+                info!("🧹 Swept {} expired reservation(s)", removed);
+            }
+        }
+    });
+}
+
+/// Either half of what a newly-accepted connection can open with
+enum IncomingRequest {
+    Relay(RelayRequestData),
+    Reserve { uuid: String, ttl_secs: u64 },
 }
 
 /// Handle incoming connection
 async fn handle_connection(
-    mut stream: TcpStream,
+    stream: TcpStream,
     addr: SocketAddr,
-    pairings: RelayPairings,
+    state: RelayState,
+    shutdown: ShutdownSignal,
 ) -> Result<()> {
-    // Read relay request message (with timeout)
-    let request = match timeout(Duration::from_secs(10), read_relay_request(&mut stream)).await {
+    let mut framed = Framed::new(stream, RelayCodec::handshake());
+
+    // If a network key is configured, challenge the peer before reading its
+    // request so `read_opening_message` can verify the MAC it answers with.
+    let server_nonce = if state.network_key.is_some() {
+        let nonce = auth::generate_server_nonce_hex();
+        framed
+            .send(Message::Challenge {
+                server_nonce: nonce.clone(),
+            })
+            .await?;
+        Some(nonce)
+    } else {
+        None
+    };
+
+    // Read the opening message (with timeout)
+    let request = match timeout(
+        Duration::from_secs(10),
+        read_opening_message(&mut framed, &state.network_key, &server_nonce),
+    )
+    .await
+    {
         Ok(Ok(req)) => req,
         Ok(Err(e)) => {
             error!("Failed to read relay request from {}: {}", addr, e);
-            send_relay_response(&mut stream, false, Some(format!("Invalid request: {}", e))).await?;
+            let _ = send_relay_response(&mut framed, false, Some(format!("Invalid request: {}", e))).await;
             return Err(e);
         }
         Err(_) => {
             error!("Timeout waiting for relay request from {}", addr);
-            send_relay_response(&mut stream, false, Some("Request timeout".to_string())).await?;
+            let _ = send_relay_response(&mut framed, false, Some("Request timeout".to_string())).await;
             anyhow::bail!("Request timeout");
         }
     };
 
+    match request {
+        IncomingRequest::Reserve { uuid, ttl_secs } => {
+            handle_reservation(framed, addr, uuid, ttl_secs, state, shutdown).await
+        }
+        IncomingRequest::Relay(request) => handle_relay_request(framed, addr, request, state, shutdown).await,
+    }
+}
+
+/// Read the opening handshake message from a freshly-accepted connection,
+/// verifying its auth MAC against `network_key` and `server_nonce` if a
+/// network key is configured.
+async fn read_opening_message(
+    framed: &mut Framed<TcpStream, RelayCodec>,
+    network_key: &Option<Vec<u8>>,
+    server_nonce: &Option<String>,
+) -> Result<IncomingRequest> {
+    match framed.next().await {
+        Some(Ok(Message::RelayRequest { uuid, peer_id, role, mode, keepalive, nonce, mac })) => {
+            verify_auth(network_key, server_nonce, &uuid, &peer_id, &role, &nonce, &mac)?;
+            Ok(IncomingRequest::Relay(RelayRequestData {
+                uuid,
+                peer_id,
+                role,
+                mode,
+                keepalive,
+            }))
+        }
+        Some(Ok(Message::Reserve { uuid, ttl_secs, nonce, mac })) => {
+            verify_auth(network_key, server_nonce, &uuid, "", "reserve", &nonce, &mac)?;
+            Ok(IncomingRequest::Reserve { uuid, ttl_secs })
+        }
+        Some(Ok(other)) => anyhow::bail!("Expected RelayRequest or Reserve, got {:?}", other),
+        Some(Err(e)) => Err(e.into()),
+        None => anyhow::bail!("Connection closed before sending a request"),
+    }
+}
+
+/// Check a peer's auth fields against the relay's configured network key.
+/// A no-op when no key is configured, so unauthenticated deployments are
+/// unaffected.
+fn verify_auth(
+    network_key: &Option<Vec<u8>>,
+    server_nonce: &Option<String>,
+    uuid: &str,
+    peer_id: &str,
+    role: &str,
+    nonce: &str,
+    mac: &str,
+) -> Result<()> {
+    let Some(key) = network_key else {
+        return Ok(());
+    };
+    let Some(expected_nonce) = server_nonce else {
+        anyhow::bail!("No challenge nonce issued for this connection");
+    };
+    if nonce != expected_nonce {
+        anyhow::bail!("Authentication failed: stale or mismatched challenge nonce");
+    }
+    if !auth::verify_mac(key, uuid, peer_id, role, expected_nonce, mac) {
+        anyhow::bail!("Authentication failed: invalid MAC");
+    }
+    Ok(())
+}
+
+/// Split a `Framed` back into its raw stream plus any bytes it had already
+/// buffered past the frame we just consumed, so a pipelined peer doesn't lose data.
+fn into_stream_and_leftover(framed: Framed<TcpStream, RelayCodec>) -> (TcpStream, BytesMut) {
+    let parts = framed.into_parts();
+    (parts.io, parts.read_buf)
+}
+
+/// Claim a UUID slot ahead of time and wait for a client to dial in and claim it
+async fn handle_reservation(
+    mut framed: Framed<TcpStream, RelayCodec>,
+    addr: SocketAddr,
+    uuid: String,
+    ttl_secs: u64,
+    state: RelayState,
+    mut shutdown: ShutdownSignal,
+) -> Result<()> {
+    let RelayState {
+        reservations,
+        metrics,
+        ..
+    } = state;
+
+    if is_draining(&shutdown) {
+        let _ = framed
+            .send(Message::RelayResponse {
+                success: false,
+                message: Some("relay draining".to_string()),
+            })
+            .await;
+        anyhow::bail!("Relay draining, rejecting new reservation for UUID {}", uuid);
+    }
+
+    let ttl = Duration::from_secs(ttl_secs).min(MAX_RESERVATION_TTL);
+    let expiry = Instant::now() + ttl;
+    let limits = Limits::default();
+
+    let (tx, mut rx) = mpsc::channel(1);
+    reservations
+        .lock()
+        .await
+        .insert(uuid.clone(), Reservation { tx, expiry });
+    metrics.pairing_pending();
+
+    info!("📌 Reserved UUID {} for {} ({}s)", uuid, addr, ttl.as_secs());
+
+    framed
+        .send(Message::ReserveResponse {
+            expiry_unix: unix_timestamp(expiry),
+            max_bytes: limits.max_bytes,
+            max_duration_secs: limits.max_duration.as_secs(),
+        })
+        .await?;
+
+    let peer_half = tokio::select! {
+        result = timeout(ttl, rx.recv()) => match result {
+            Ok(Some(half)) => half,
+            Ok(None) => {
+                metrics.pairing_no_longer_pending();
+                error!("Reservation channel closed for UUID {}", uuid);
+                reservations.lock().await.remove(&uuid);
+                anyhow::bail!("Reservation failed");
+            }
+            Err(_) => {
+                metrics.pairing_no_longer_pending();
+                warn!("Reservation expired for UUID {} after {}s", uuid, ttl.as_secs());
+                reservations.lock().await.remove(&uuid);
+                anyhow::bail!("Reservation expired");
+            }
+        },
+        _ = shutdown.changed() => {
+            metrics.pairing_no_longer_pending();
+            reservations.lock().await.remove(&uuid);
+            let _ = framed
+                .send(Message::RelayResponse {
+                    success: false,
+                    message: Some("relay draining".to_string()),
+                })
+                .await;
+            anyhow::bail!("Relay draining, abandoning reservation for UUID {}", uuid);
+        }
+    };
+    metrics.pairing_no_longer_pending();
+
     info!(
-        "Relay request from {}: UUID={}, peer_id={}, role={}",
-        addr, request.uuid, request.peer_id, request.role
+        "🔗 Reserved UUID {} claimed by {} ({})",
+        uuid, peer_half.peer_addr, peer_half.role
     );
 
-    // Try to pair this connection
-    let uuid = request.uuid.clone();
-    let mut pairings_guard = pairings.lock().await;
+    let (stream, leftover) = into_stream_and_leftover(framed);
+    let host_half = ConnectionHalf {
+        stream,
+        peer_addr: addr,
+        role: "host".to_string(),
+        peer_id: String::new(),
+        // `Reserve` predates keepalive and has no field to negotiate it, so
+        // assume the conservative default: a raw, transparent byte stream.
+        keepalive: false,
+        leftover,
+    };
 
-    if let Some(tx) = pairings_guard.remove(&uuid) {
-        // Found waiting peer - send this connection to complete the pair
-        info!("✓ Pairing connection for UUID {}: {} ({})", uuid, addr, request.role);
+    proxy_connections(host_half, peer_half, limits, metrics).await
+}
+
+/// Handle a `RelayRequest`: pair against a live waiter, claim a standing
+/// reservation, or start waiting for a peer to show up.
+async fn handle_relay_request(
+    mut framed: Framed<TcpStream, RelayCodec>,
+    addr: SocketAddr,
+    request: RelayRequestData,
+    state: RelayState,
+    mut shutdown: ShutdownSignal,
+) -> Result<()> {
+    info!(
+        "Relay request from {}: UUID={}, peer_id={}, role={}, mode={}",
+        addr, request.uuid, request.peer_id, request.role, request.mode
+    );
 
-        drop(pairings_guard); // Release lock before async operations
+    if request.mode == "room" {
+        return handle_room_join(framed, addr, request, state.rooms, state.metrics, shutdown).await;
+    }
 
-        // Send success response
-        send_relay_response(&mut stream, true, None).await?;
+    let RelayState {
+        pairings,
+        reservations,
+        metrics,
+        ..
+    } = state;
 
-        // Send this connection half to the waiting peer
+    let uuid = request.uuid.clone();
+
+    // A live, already-waiting peer takes priority over a standing reservation.
+    if let Some(tx) = pairings.lock().await.remove(&uuid) {
+        info!("✓ Pairing connection for UUID {}: {} ({})", uuid, addr, request.role);
+        send_relay_response(&mut framed, true, None).await?;
+
+        let (stream, leftover) = into_stream_and_leftover(framed);
         let half = ConnectionHalf {
             stream,
             peer_addr: addr,
             role: request.role.clone(),
             peer_id: request.peer_id.clone(),
+            keepalive: request.keepalive,
+            leftover,
         };
 
         if tx.send(half).await.is_err() {
@@ -137,90 +549,158 @@ async fn handle_connection(
         }
 
         info!("🔗 Connection paired successfully for UUID {}", uuid);
-    } else {
-        // First connection for this UUID - wait for peer
-        info!("⏳ Waiting for peer to complete pairing for UUID {}", uuid);
+        return Ok(());
+    }
+
+    if let Some(reservation) = reservations.lock().await.remove(&uuid) {
+        info!("✓ Claiming reservation for UUID {}: {} ({})", uuid, addr, request.role);
+        send_relay_response(&mut framed, true, None).await?;
 
-        // Create channel for receiving the paired connection
-        let (tx, mut rx) = mpsc::channel(1);
-        pairings_guard.insert(uuid.clone(), tx);
-        drop(pairings_guard); // Release lock
+        let (stream, leftover) = into_stream_and_leftover(framed);
+        let half = ConnectionHalf {
+            stream,
+            peer_addr: addr,
+            role: request.role.clone(),
+            peer_id: request.peer_id.clone(),
+            keepalive: request.keepalive,
+            leftover,
+        };
+
+        if reservation.tx.send(half).await.is_err() {
+            error!("Failed to send connection half for reserved UUID {}", uuid);
+            anyhow::bail!("Reservation channel closed");
+        }
 
-        // Send success response
-        send_relay_response(&mut stream, true, None).await?;
+        info!("🔗 Reserved UUID {} claimed successfully", uuid);
+        return Ok(());
+    }
+
+    if is_draining(&shutdown) {
+        send_relay_response(&mut framed, false, Some("relay draining".to_string())).await?;
+        anyhow::bail!("Relay draining, rejecting new pairing for UUID {}", uuid);
+    }
+
+    // First connection for this UUID - wait for peer
+    info!("⏳ Waiting for peer to complete pairing for UUID {}", uuid);
 
-        // Wait for peer connection (with timeout)
-        let peer_half = match timeout(PAIRING_TIMEOUT, rx.recv()).await {
+    // Create channel for receiving the paired connection
+    let (tx, mut rx) = mpsc::channel(1);
+    pairings.lock().await.insert(uuid.clone(), tx);
+    metrics.pairing_pending();
+
+    // Send success response
+    send_relay_response(&mut framed, true, None).await?;
+
+    // Wait for peer connection (with timeout)
+    let peer_half = tokio::select! {
+        result = timeout(PAIRING_TIMEOUT, rx.recv()) => match result {
             Ok(Some(half)) => half,
             Ok(None) => {
+                metrics.pairing_no_longer_pending();
                 error!("Pairing channel closed for UUID {}", uuid);
                 pairings.lock().await.remove(&uuid);
                 anyhow::bail!("Pairing failed");
             }
             Err(_) => {
+                metrics.pairing_no_longer_pending();
+                metrics.pairing_timed_out();
                 warn!("Pairing timeout for UUID {} after {}s", uuid, PAIRING_TIMEOUT.as_secs());
                 pairings.lock().await.remove(&uuid);
                 anyhow::bail!("Pairing timeout");
             }
-        };
-
-        info!(
-            "🔗 Paired UUID {}: {} ({}) <-> {} ({})",
-            uuid, addr, request.role, peer_half.peer_addr, peer_half.role
-        );
+        },
+        _ = shutdown.changed() => {
+            metrics.pairing_no_longer_pending();
+            pairings.lock().await.remove(&uuid);
+            warn!("Abandoning pairing wait for UUID {}: relay draining", uuid);
+            let _ = framed
+                .send(Message::RelayResponse {
+                    success: false,
+                    message: Some("relay draining".to_string()),
+                })
+                .await;
+            anyhow::bail!("Relay draining, abandoning pairing wait for UUID {}", uuid);
+        }
+    };
+    metrics.pairing_no_longer_pending();
 
-        // Start bidirectional proxy
-        let conn1 = ConnectionHalf {
-            stream,
-            peer_addr: addr,
-            role: request.role.clone(),
-            peer_id: request.peer_id.clone(),
-        };
+    info!(
+        "🔗 Paired UUID {}: {} ({}) <-> {} ({})",
+        uuid, addr, request.role, peer_half.peer_addr, peer_half.role
+    );
 
-        proxy_connections(conn1, peer_half).await?;
-    }
+    // Start bidirectional proxy
+    let (stream, leftover) = into_stream_and_leftover(framed);
+    let conn1 = ConnectionHalf {
+        stream,
+        peer_addr: addr,
+        role: request.role.clone(),
+        peer_id: request.peer_id.clone(),
+        keepalive: request.keepalive,
+        leftover,
+    };
 
-    Ok(())
+    proxy_connections(conn1, peer_half, Limits::default(), metrics).await
 }
 
-/// Read relay request message from stream
-async fn read_relay_request(stream: &mut TcpStream) -> Result<RelayRequestData> {
-    // Read length prefix (4 bytes)
-    let mut len_buf = [0u8; 4];
-    stream.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
+/// Handle a `RelayRequest` with `mode: "room"`: join (or create) the
+/// broadcast room for this UUID instead of waiting for exactly one peer.
+async fn handle_room_join(
+    mut framed: Framed<TcpStream, RelayCodec>,
+    addr: SocketAddr,
+    request: RelayRequestData,
+    rooms: room::Rooms,
+    metrics: Arc<Metrics>,
+    shutdown: ShutdownSignal,
+) -> Result<()> {
+    info!(
+        "🚪 Room join from {}: UUID={}, peer_id={}, role={}",
+        addr, request.uuid, request.peer_id, request.role
+    );
 
-    // Validate length
-    if len > 1024 * 1024 {
-        anyhow::bail!("Message too large: {} bytes", len);
+    if is_draining(&shutdown) {
+        send_relay_response(&mut framed, false, Some("relay draining".to_string())).await?;
+        anyhow::bail!("Relay draining, rejecting new room join for UUID {}", request.uuid);
     }
 
-    // Read message data
-    let mut data = vec![0u8; len];
-    stream.read_exact(&mut data).await?;
+    send_relay_response(&mut framed, true, None).await?;
 
-    // Deserialize message
-    let msg: Message = serde_json::from_slice(&data)?;
+    let (stream, leftover) = into_stream_and_leftover(framed);
+    let half = ConnectionHalf {
+        stream,
+        peer_addr: addr,
+        role: request.role,
+        peer_id: request.peer_id,
+        keepalive: request.keepalive,
+        leftover,
+    };
 
-    // Extract relay request
-    match msg {
-        Message::RelayRequest { uuid, peer_id, role } => {
-            Ok(RelayRequestData { uuid, peer_id, role })
-        }
-        _ => anyhow::bail!("Expected RelayRequest, got {:?}", msg),
+    room::join_room(half, request.uuid, rooms, Limits::default(), metrics).await
+}
+
+/// Convert a future `Instant` into a wall-clock unix timestamp (seconds)
+fn unix_timestamp(instant: Instant) -> u64 {
+    let now_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let now_instant = Instant::now();
+    if instant >= now_instant {
+        now_unix + (instant - now_instant).as_secs()
+    } else {
+        now_unix.saturating_sub((now_instant - instant).as_secs())
     }
 }
 
-/// Send relay response message
+/// Send a `RelayResponse` over the handshake-phase framed connection
 async fn send_relay_response(
-    stream: &mut TcpStream,
+    framed: &mut Framed<TcpStream, RelayCodec>,
     success: bool,
     message: Option<String>,
 ) -> Result<()> {
-    let msg = Message::RelayResponse { success, message };
-    let bytes = msg.to_bytes()?;
-    stream.write_all(&bytes).await?;
-    stream.flush().await?;
+    framed
+        .send(Message::RelayResponse { success, message })
+        .await?;
     Ok(())
 }
 
@@ -229,68 +709,312 @@ struct RelayRequestData {
     uuid: String,
     peer_id: String,
     role: String,
+    /// `"pair"` for the original strict two-party pairing, or `"room"` to
+    /// join an N-party broadcast room sharing this UUID
+    mode: String,
+    keepalive: bool,
+}
+
+/// Proxy data bidirectionally between two connections, enforcing the given caps
+async fn proxy_connections(
+    conn1: ConnectionHalf,
+    conn2: ConnectionHalf,
+    limits: Limits,
+    metrics: Arc<Metrics>,
+) -> Result<()> {
+    let started_at = Instant::now();
+    metrics.circuit_started();
+
+    let result = if conn1.keepalive && conn2.keepalive {
+        proxy_connections_keepalive(conn1, conn2, limits, &metrics).await
+    } else {
+        proxy_connections_raw(conn1, conn2, limits, &metrics).await
+    };
+
+    metrics.circuit_closed(started_at.elapsed());
+    result
 }
 
-/// Proxy data bidirectionally between two connections
-async fn proxy_connections(mut conn1: ConnectionHalf, mut conn2: ConnectionHalf) -> Result<()> {
+/// Proxy data bidirectionally as a raw byte stream, with no liveness detection
+async fn proxy_connections_raw(
+    mut conn1: ConnectionHalf,
+    mut conn2: ConnectionHalf,
+    limits: Limits,
+    metrics: &Metrics,
+) -> Result<()> {
     info!(
         "🔄 Starting proxy: {} ({}) <-> {} ({})",
         conn1.peer_addr, conn1.role, conn2.peer_addr, conn2.role
     );
 
+    let mut total_bytes_1_to_2 = 0u64;
+    let mut total_bytes_2_to_1 = 0u64;
+
     let (mut r1, mut w1) = conn1.stream.split();
     let (mut r2, mut w2) = conn2.stream.split();
 
+    // Forward anything the codec had already buffered past the handshake frame
+    if !conn1.leftover.is_empty() && w2.write_all(&conn1.leftover).await.is_ok() {
+        total_bytes_1_to_2 += conn1.leftover.len() as u64;
+        metrics.add_bytes_1_to_2(conn1.leftover.len() as u64);
+    }
+    if !conn2.leftover.is_empty() && w1.write_all(&conn2.leftover).await.is_ok() {
+        total_bytes_2_to_1 += conn2.leftover.len() as u64;
+        metrics.add_bytes_2_to_1(conn2.leftover.len() as u64);
+    }
+
     let mut buf1 = vec![0u8; 64 * 1024]; // 64KB buffer
     let mut buf2 = vec![0u8; 64 * 1024];
 
+    let pump = async {
+        loop {
+            tokio::select! {
+                // Read from conn1, write to conn2
+                result = r1.read(&mut buf1) => {
+                    match result {
+                        Ok(0) => {
+                            info!("Connection {} ({}) closed", conn1.peer_addr, conn1.role);
+                            break;
+                        }
+                        Ok(n) => {
+                            if let Err(e) = w2.write_all(&buf1[..n]).await {
+                                error!("Write error to {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                                break;
+                            }
+                            total_bytes_1_to_2 += n as u64;
+                            metrics.add_bytes_1_to_2(n as u64);
+                            if total_bytes_1_to_2 + total_bytes_2_to_1 > limits.max_bytes {
+                                warn!(
+                                    "Circuit {} <-> {} exceeded byte cap of {} bytes, tearing down",
+                                    conn1.peer_addr, conn2.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Read error from {} ({}): {}", conn1.peer_addr, conn1.role, e);
+                            break;
+                        }
+                    }
+                }
+
+                // Read from conn2, write to conn1
+                result = r2.read(&mut buf2) => {
+                    match result {
+                        Ok(0) => {
+                            info!("Connection {} ({}) closed", conn2.peer_addr, conn2.role);
+                            break;
+                        }
+                        Ok(n) => {
+                            if let Err(e) = w1.write_all(&buf2[..n]).await {
+                                error!("Write error to {} ({}): {}", conn1.peer_addr, conn1.role, e);
+                                break;
+                            }
+                            total_bytes_2_to_1 += n as u64;
+                            metrics.add_bytes_2_to_1(n as u64);
+                            if total_bytes_1_to_2 + total_bytes_2_to_1 > limits.max_bytes {
+                                warn!(
+                                    "Circuit {} <-> {} exceeded byte cap of {} bytes, tearing down",
+                                    conn1.peer_addr, conn2.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Read error from {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    if tokio::time::timeout(limits.max_duration, pump).await.is_err() {
+        warn!(
+            "Circuit {} <-> {} exceeded duration cap of {}s, tearing down",
+            conn1.peer_addr,
+            conn2.peer_addr,
+            limits.max_duration.as_secs()
+        );
+    }
+
+    info!(
+        "🔌 Proxy closed: {} -> {}: {} bytes, {} -> {}: {} bytes",
+        conn1.role, conn2.role, total_bytes_1_to_2,
+        conn2.role, conn1.role, total_bytes_2_to_1
+    );
+
+    Ok(())
+}
+
+/// Proxy two connections that both negotiated `keepalive: true`: frames are
+/// decoded via `RelayCodec` so the relay can intercept Ping/Pong itself and
+/// detect a peer that has gone silent, rather than just pumping raw bytes
+/// until a read errors. Real application traffic travels as `Message::Data`
+/// frames, forwarded verbatim to the other side alongside the Ping/Pong
+/// interleave.
+async fn proxy_connections_keepalive(
+    mut conn1: ConnectionHalf,
+    mut conn2: ConnectionHalf,
+    limits: Limits,
+    metrics: &Metrics,
+) -> Result<()> {
+    info!(
+        "🔄 Starting keepalive-aware proxy: {} ({}) <-> {} ({})",
+        conn1.peer_addr, conn1.role, conn2.peer_addr, conn2.role
+    );
+
+    let leftover1 = std::mem::take(&mut conn1.leftover);
+    let leftover2 = std::mem::take(&mut conn2.leftover);
+
+    let mut parts1 = FramedParts::new(conn1.stream, RelayCodec::default());
+    parts1.read_buf = leftover1;
+    let (mut sink1, mut stream1) = Framed::from_parts(parts1).split();
+
+    let mut parts2 = FramedParts::new(conn2.stream, RelayCodec::default());
+    parts2.read_buf = leftover2;
+    let (mut sink2, mut stream2) = Framed::from_parts(parts2).split();
+
     let mut total_bytes_1_to_2 = 0u64;
     let mut total_bytes_2_to_1 = 0u64;
 
-    loop {
-        tokio::select! {
-            // Read from conn1, write to conn2
-            result = r1.read(&mut buf1) => {
-                match result {
-                    Ok(0) => {
-                        info!("Connection {} ({}) closed", conn1.peer_addr, conn1.role);
-                        break;
-                    }
-                    Ok(n) => {
-                        if let Err(e) = w2.write_all(&buf1[..n]).await {
-                            error!("Write error to {} ({}): {}", conn2.peer_addr, conn2.role, e);
+    let mut last_activity_1 = Instant::now();
+    let mut last_activity_2 = Instant::now();
+    let mut keepalive_ticker = tokio::time::interval(KEEPALIVE_CHECK_INTERVAL);
+
+    let pump = async {
+        loop {
+            tokio::select! {
+                // Read from conn1, forward decoded frames to conn2
+                msg = stream1.next() => {
+                    match msg {
+                        None => {
+                            info!("Connection {} ({}) closed", conn1.peer_addr, conn1.role);
+                            break;
+                        }
+                        Some(Err(e)) => {
+                            error!("Read error from {} ({}): {}", conn1.peer_addr, conn1.role, e);
                             break;
                         }
-                        total_bytes_1_to_2 += n as u64;
+                        Some(Ok(Message::Ping)) => {
+                            last_activity_1 = Instant::now();
+                            if let Err(e) = sink1.send(Message::Pong).await {
+                                error!("Failed to send Pong to {} ({}): {}", conn1.peer_addr, conn1.role, e);
+                                break;
+                            }
+                        }
+                        Some(Ok(Message::Pong)) => {
+                            last_activity_1 = Instant::now();
+                        }
+                        // Real application traffic (almost always `Message::Data`)
+                        // passed straight through to the other side.
+                        Some(Ok(other)) => {
+                            last_activity_1 = Instant::now();
+                            let len = frame_len(&other);
+                            total_bytes_1_to_2 += len;
+                            metrics.add_bytes_1_to_2(len);
+                            if let Err(e) = sink2.send(other).await {
+                                error!("Write error to {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                                break;
+                            }
+                            if total_bytes_1_to_2 + total_bytes_2_to_1 > limits.max_bytes {
+                                warn!(
+                                    "Circuit {} <-> {} exceeded byte cap of {} bytes, tearing down",
+                                    conn1.peer_addr, conn2.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                        }
                     }
-                    Err(e) => {
-                        error!("Read error from {} ({}): {}", conn1.peer_addr, conn1.role, e);
-                        break;
+                }
+
+                // Read from conn2, forward decoded frames to conn1
+                msg = stream2.next() => {
+                    match msg {
+                        None => {
+                            info!("Connection {} ({}) closed", conn2.peer_addr, conn2.role);
+                            break;
+                        }
+                        Some(Err(e)) => {
+                            error!("Read error from {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                            break;
+                        }
+                        Some(Ok(Message::Ping)) => {
+                            last_activity_2 = Instant::now();
+                            if let Err(e) = sink2.send(Message::Pong).await {
+                                error!("Failed to send Pong to {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                                break;
+                            }
+                        }
+                        Some(Ok(Message::Pong)) => {
+                            last_activity_2 = Instant::now();
+                        }
+                        // Real application traffic (almost always `Message::Data`)
+                        // passed straight through to the other side.
+                        Some(Ok(other)) => {
+                            last_activity_2 = Instant::now();
+                            let len = frame_len(&other);
+                            total_bytes_2_to_1 += len;
+                            metrics.add_bytes_2_to_1(len);
+                            if let Err(e) = sink1.send(other).await {
+                                error!("Write error to {} ({}): {}", conn1.peer_addr, conn1.role, e);
+                                break;
+                            }
+                            if total_bytes_1_to_2 + total_bytes_2_to_1 > limits.max_bytes {
+                                warn!(
+                                    "Circuit {} <-> {} exceeded byte cap of {} bytes, tearing down",
+                                    conn1.peer_addr, conn2.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                        }
                     }
                 }
-            }
 
-            // Read from conn2, write to conn1
-            result = r2.read(&mut buf2) => {
-                match result {
-                    Ok(0) => {
-                        info!("Connection {} ({}) closed", conn2.peer_addr, conn2.role);
+                // Ping idle sides, and give up on sides that never answer
+                _ = keepalive_ticker.tick() => {
+                    let now = Instant::now();
+
+                    if now.duration_since(last_activity_1) > IDLE_TIMEOUT {
+                        warn!(
+                            "Idle disconnect: {} ({}) sent nothing for {}s",
+                            conn1.peer_addr, conn1.role, IDLE_TIMEOUT.as_secs()
+                        );
+                        break;
+                    }
+                    if now.duration_since(last_activity_2) > IDLE_TIMEOUT {
+                        warn!(
+                            "Idle disconnect: {} ({}) sent nothing for {}s",
+                            conn2.peer_addr, conn2.role, IDLE_TIMEOUT.as_secs()
+                        );
                         break;
                     }
-                    Ok(n) => {
-                        if let Err(e) = w1.write_all(&buf2[..n]).await {
-                            error!("Write error to {} ({}): {}", conn1.peer_addr, conn1.role, e);
+
+                    if now.duration_since(last_activity_1) > KEEPALIVE_INTERVAL {
+                        if let Err(e) = sink1.send(Message::Ping).await {
+                            error!("Failed to send Ping to {} ({}): {}", conn1.peer_addr, conn1.role, e);
                             break;
                         }
-                        total_bytes_2_to_1 += n as u64;
                     }
-                    Err(e) => {
-                        error!("Read error from {} ({}): {}", conn2.peer_addr, conn2.role, e);
-                        break;
+                    if now.duration_since(last_activity_2) > KEEPALIVE_INTERVAL {
+                        if let Err(e) = sink2.send(Message::Ping).await {
+                            error!("Failed to send Ping to {} ({}): {}", conn2.peer_addr, conn2.role, e);
+                            break;
+                        }
                     }
                 }
             }
         }
+    };
+
+    if tokio::time::timeout(limits.max_duration, pump).await.is_err() {
+        warn!(
+            "Circuit {} <-> {} exceeded duration cap of {}s, tearing down",
+            conn1.peer_addr,
+            conn2.peer_addr,
+            limits.max_duration.as_secs()
+        );
     }
 
     info!(
@@ -301,3 +1025,8 @@ async fn proxy_connections(mut conn1: ConnectionHalf, mut conn2: ConnectionHalf)
 
     Ok(())
 }
+
+/// Approximate wire size of a decoded frame, for the byte-cap accounting
+fn frame_len(msg: &Message) -> u64 {
+    serde_json::to_vec(msg).map(|b| 4 + b.len() as u64).unwrap_or(0)
+}
@@ -0,0 +1,237 @@
+/// Metrics/observability subsystem
+///
+/// Tracks relay health with plain atomics and serves them over a small
+/// second HTTP port in Prometheus text exposition format, plus a `/healthz`.
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{error, info};
+
+/// Upper bound (inclusive) of each circuit-duration histogram bucket, in seconds.
+/// A final `+Inf` bucket catches anything longer.
+const HISTOGRAM_BUCKETS_SECS: [f64; 8] = [1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// Shared counters/gauges for the relay's health, safe to update from any task
+pub struct Metrics {
+    active_pairings: AtomicI64,
+    pending_uuids: AtomicI64,
+    connections_accepted: AtomicU64,
+    pairing_timeouts: AtomicU64,
+    bytes_1_to_2: AtomicU64,
+    bytes_2_to_1: AtomicU64,
+    circuit_duration_buckets: [AtomicU64; HISTOGRAM_BUCKETS_SECS.len() + 1],
+    circuit_duration_sum_millis: AtomicU64,
+    circuit_duration_count: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            active_pairings: AtomicI64::new(0),
+            pending_uuids: AtomicI64::new(0),
+            connections_accepted: AtomicU64::new(0),
+            pairing_timeouts: AtomicU64::new(0),
+            bytes_1_to_2: AtomicU64::new(0),
+            bytes_2_to_1: AtomicU64::new(0),
+            circuit_duration_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            circuit_duration_sum_millis: AtomicU64::new(0),
+            circuit_duration_count: AtomicU64::new(0),
+        }
+    }
+
+    /// A raw TCP connection was accepted on the relay port
+    pub fn connection_accepted(&self) {
+        self.connections_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A UUID started waiting for its peer (either a fresh pairing or a reservation)
+    pub fn pairing_pending(&self) {
+        self.pending_uuids.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The wait for a UUID ended, one way or another
+    pub fn pairing_no_longer_pending(&self) {
+        self.pending_uuids.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// A wait for a peer hit `PAIRING_TIMEOUT`
+    pub fn pairing_timed_out(&self) {
+        self.pairing_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A circuit started proxying
+    pub fn circuit_started(&self) {
+        self.active_pairings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A circuit finished proxying after running for `duration`
+    pub fn circuit_closed(&self, duration: Duration) {
+        self.active_pairings.fetch_sub(1, Ordering::Relaxed);
+
+        let secs = duration.as_secs_f64();
+        let bucket = HISTOGRAM_BUCKETS_SECS
+            .iter()
+            .position(|&boundary| secs <= boundary)
+            .unwrap_or(HISTOGRAM_BUCKETS_SECS.len());
+        self.circuit_duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.circuit_duration_sum_millis
+            .fetch_add((secs * 1000.0) as u64, Ordering::Relaxed);
+        self.circuit_duration_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of circuits currently being proxied
+    pub fn active_circuits(&self) -> i64 {
+        self.active_pairings.load(Ordering::Relaxed)
+    }
+
+    pub fn add_bytes_1_to_2(&self, n: u64) {
+        self.bytes_1_to_2.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_2_to_1(&self, n: u64) {
+        self.bytes_2_to_1.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# TYPE relay_active_pairings gauge\n");
+        out.push_str(&format!(
+            "relay_active_pairings {}\n",
+            self.active_pairings.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# TYPE relay_pending_uuids gauge\n");
+        out.push_str(&format!(
+            "relay_pending_uuids {}\n",
+            self.pending_uuids.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# TYPE relay_connections_accepted_total counter\n");
+        out.push_str(&format!(
+            "relay_connections_accepted_total {}\n",
+            self.connections_accepted.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# TYPE relay_pairing_timeouts_total counter\n");
+        out.push_str(&format!(
+            "relay_pairing_timeouts_total {}\n",
+            self.pairing_timeouts.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# TYPE relay_bytes_relayed_total counter\n");
+        out.push_str(&format!(
+            "relay_bytes_relayed_total{{direction=\"1_to_2\"}} {}\n",
+            self.bytes_1_to_2.load(Ordering::Relaxed)
+        ));
+        out.push_str(&format!(
+            "relay_bytes_relayed_total{{direction=\"2_to_1\"}} {}\n",
+            self.bytes_2_to_1.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# TYPE relay_circuit_duration_seconds histogram\n");
+        let mut cumulative = 0u64;
+        for (bucket, boundary) in HISTOGRAM_BUCKETS_SECS.iter().enumerate() {
+            cumulative += self.circuit_duration_buckets[bucket].load(Ordering::Relaxed);
+            out.push_str(&format!(
+                "relay_circuit_duration_seconds_bucket{{le=\"{}\"}} {}\n",
+                boundary, cumulative
+            ));
+        }
+        cumulative += self.circuit_duration_buckets[HISTOGRAM_BUCKETS_SECS.len()].load(Ordering::Relaxed);
+        out.push_str(&format!(
+            "relay_circuit_duration_seconds_bucket{{le=\"+Inf\"}} {}\n",
+            cumulative
+        ));
+        out.push_str(&format!(
+            "relay_circuit_duration_seconds_sum {:.3}\n",
+            self.circuit_duration_sum_millis.load(Ordering::Relaxed) as f64 / 1000.0
+        ));
+        out.push_str(&format!(
+            "relay_circuit_duration_seconds_count {}\n",
+            self.circuit_duration_count.load(Ordering::Relaxed)
+        ));
+
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serve `/metrics` (Prometheus text format) and `/healthz` on `port`
+pub fn spawn_metrics_server(metrics: Arc<Metrics>, port: u16) {
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(format!("0.0.0.0:{}", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to bind metrics server on port {}: {}", port, e);
+                return;
+            }
+        };
+
+        info!("📈 Metrics server listening on 0.0.0.0:{}", port);
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, _addr)) => {
+                    let metrics = Arc::clone(&metrics);
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_metrics_request(stream, &metrics).await {
+                            error!("Metrics request error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept metrics connection: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Handle a single HTTP/1.1 request against the metrics listener
+async fn serve_metrics_request(stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Drain the remaining header lines up to the blank line ending the request
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = reader.read_line(&mut line).await?;
+        if n == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+
+    let (status_line, content_type, body) = if request_line.starts_with("GET /metrics ") {
+        ("200 OK", "text/plain; version=0.0.4", metrics.render_prometheus())
+    } else if request_line.starts_with("GET /healthz ") {
+        ("200 OK", "text/plain", "ok\n".to_string())
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status_line,
+        content_type,
+        body.len(),
+        body
+    );
+
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
@@ -2,14 +2,34 @@
 ///
 /// This module defines only the message types needed for the relay server.
 /// The full protocol is in the main remotely application.
+///
+/// Wire framing lives in `codec::RelayCodec`, not here.
 
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
-use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 /// Maximum message size (10MB)
 pub const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
 
+/// Default for `RelayRequest::keepalive` when the field is omitted: peers
+/// that predate this feature get today's behavior, a transparent byte-stream
+/// pair with no framing assumptions placed on their payload
+fn default_keepalive() -> bool {
+    false
+}
+
+/// Default for the auth handshake fields when the peer predates
+/// pre-shared-key authentication (and when authentication is disabled,
+/// since the relay simply won't check them)
+fn default_auth_field() -> String {
+    String::new()
+}
+
+/// Default for `RelayRequest::mode` when the field is omitted: strict
+/// two-party pairing, the relay's original behavior
+fn default_mode() -> String {
+    "pair".to_string()
+}
+
 /// Message types for relay communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
@@ -21,6 +41,23 @@ pub enum Message {
         peer_id: String,
         /// Role: "client" or "host"
         role: String,
+        /// Pairing mode: `"pair"` for the original strict two-party pairing,
+        /// or `"room"` to join an N-party broadcast room sharing this UUID
+        #[serde(default = "default_mode")]
+        mode: String,
+        /// Whether this peer understands the Ping/Pong keepalive frames;
+        /// the relay only enables keepalive on a circuit where both halves opt in
+        #[serde(default = "default_keepalive")]
+        keepalive: bool,
+        /// The `server_nonce` from the `Challenge` this peer is responding to;
+        /// only checked when the relay has a network key configured
+        #[serde(default = "default_auth_field")]
+        nonce: String,
+        /// HMAC-SHA256 over the length-prefixed fields
+        /// `uuid || peer_id || role || server_nonce` (see `auth::verify_mac`),
+        /// keyed by the pre-shared network key, hex-encoded
+        #[serde(default = "default_auth_field")]
+        mac: String,
     },
     RelayResponse {
         /// Whether relay pairing succeeded
@@ -28,117 +65,55 @@ pub enum Message {
         /// Optional error message
         message: Option<String>,
     },
-}
-
-impl Message {
-    /// Serialize message to bytes with length prefix
-    ///
-    /// Format: [length: u32][payload: JSON]
-    pub fn to_bytes(&self) -> Result<Bytes> {
-        // Serialize to JSON
-        let json = serde_json::to_vec(self)?;
-
-        // Create buffer with length prefix
-        let mut buf = BytesMut::with_capacity(4 + json.len());
-        buf.put_u32(json.len() as u32);
-        buf.put_slice(&json);
-
-        Ok(buf.freeze())
-    }
-
-    /// Deserialize message from bytes
-    pub fn from_bytes(mut data: Bytes) -> Result<Self> {
-        // Check minimum length
-        if data.len() < 4 {
-            anyhow::bail!("Message too short");
-        }
-
-        // Read length
-        let len = data.get_u32() as usize;
-
-        // Validate length
-        if len > MAX_MESSAGE_SIZE {
-            anyhow::bail!("Message too large: {} bytes", len);
-        }
-
-        if data.remaining() < len {
-            anyhow::bail!("Incomplete message: expected {}, got {}", len, data.remaining());
-        }
-
-        // Deserialize JSON
-        let msg: Message = serde_json::from_slice(&data[..len])?;
-        Ok(msg)
-    }
-}
-
-/// Message framing for stream-based transport
-pub struct MessageFramer {
-    /// Buffer for partial messages
-    buffer: BytesMut,
-}
-
-impl MessageFramer {
-    pub fn new() -> Self {
-        Self {
-            buffer: BytesMut::with_capacity(65536),
-        }
-    }
-
-    /// Add data to buffer and try to extract complete messages
-    pub fn feed(&mut self, data: &[u8]) -> Vec<Message> {
-        self.buffer.extend_from_slice(data);
-
-        let mut messages = Vec::new();
-
-        loop {
-            // Need at least 4 bytes for length prefix
-            if self.buffer.len() < 4 {
-                break;
-            }
-
-            // Peek at length without consuming
-            let len = u32::from_be_bytes([
-                self.buffer[0],
-                self.buffer[1],
-                self.buffer[2],
-                self.buffer[3],
-            ]) as usize;
-
-            // Validate length
-            if len > MAX_MESSAGE_SIZE {
-                tracing::error!("Invalid message length: {}, clearing buffer", len);
-                self.buffer.clear();
-                break;
-            }
-
-            // Check if we have complete message (length prefix + data)
-            if self.buffer.len() < 4 + len {
-                break; // Wait for more data
-            }
-
-            // Extract message bytes (including length prefix)
-            let msg_bytes = self.buffer.split_to(4 + len);
-
-            // Try to deserialize
-            match Message::from_bytes(msg_bytes.freeze()) {
-                Ok(msg) => messages.push(msg),
-                Err(e) => {
-                    tracing::error!("Failed to deserialize message: {}", e);
-                }
-            }
-        }
-
-        messages
-    }
-
-    /// Clear buffer
-    pub fn clear(&mut self) {
-        self.buffer.clear();
-    }
-}
-
-impl Default for MessageFramer {
-    fn default() -> Self {
-        Self::new()
-    }
+    /// Liveness probe sent by the relay to an idle, keepalive-aware peer
+    Ping,
+    /// Reply to `Ping`
+    Pong,
+    /// Opaque application payload, carried alongside `Ping`/`Pong` once a
+    /// circuit has negotiated `keepalive: true` on both sides. Neither peer
+    /// nor the relay interprets the bytes; a keepalive-aware peer wraps its
+    /// real protocol traffic in this variant instead of writing it to the
+    /// socket unframed, since the other control-plane variants have no
+    /// generic "just bytes" shape of their own.
+    Data(Vec<u8>),
+    /// Sent by the relay immediately on accept when a network key is
+    /// configured; the peer must echo `server_nonce` back in its MAC
+    Challenge {
+        /// Random per-connection nonce, hex-encoded
+        server_nonce: String,
+    },
+    /// Claim a UUID slot ahead of time, before any peer has dialed in
+    Reserve {
+        /// Unique identifier to reserve
+        uuid: String,
+        /// Requested time-to-live for the reservation, in seconds
+        ttl_secs: u64,
+        /// The `server_nonce` from the `Challenge` this peer is responding to
+        #[serde(default = "default_auth_field")]
+        nonce: String,
+        /// HMAC-SHA256 over the length-prefixed fields
+        /// `uuid || "" || "reserve" || server_nonce`, i.e. an empty `peer_id`
+        /// and `"reserve"` as the role (see `auth::verify_mac`), hex-encoded
+        #[serde(default = "default_auth_field")]
+        mac: String,
+    },
+    /// Reply to `Reserve` describing the caps the relay will enforce
+    ReserveResponse {
+        /// Unix timestamp (seconds) at which the reservation expires
+        expiry_unix: u64,
+        /// Maximum cumulative bytes (both directions) the circuit may relay
+        max_bytes: u64,
+        /// Maximum lifetime of the circuit once paired, in seconds
+        max_duration_secs: u64,
+    },
+    /// Broadcast to the rest of a room when a new member joins
+    PeerJoined {
+        /// The id the relay assigned the new member for this room
+        conn_id: u64,
+    },
+    /// Broadcast to the rest of a room when a member disconnects
+    PeerLeft {
+        /// The id of the member that left
+        conn_id: u64,
+    },
 }
@@ -0,0 +1,182 @@
+/// N-party "room" relays
+///
+/// A room is a UUID shared by more than the usual two peers: instead of the
+/// strict-pairing byte pump in `proxy_connections`, every member gets a
+/// `ConnId` and a clone of a `broadcast::Sender`. Each member's task reads
+/// framed messages off its own socket and republishes them to the room
+/// (tagged with its own id), while forwarding out everything anyone else
+/// publishes, skipping its own messages.
+use crate::codec::RelayCodec;
+use crate::metrics::Metrics;
+use crate::protocol::Message;
+use crate::{ConnectionHalf, Limits};
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::Instant;
+use tokio_util::codec::{Framed, FramedParts};
+use tracing::{error, info, warn};
+
+/// Identifies one member of a room for the lifetime of its membership
+pub type ConnId = u64;
+
+/// Bounded so one slow member can't make the room buffer unboundedly; a
+/// member that falls this far behind gets `RecvError::Lagged` and just
+/// misses the messages it couldn't keep up with.
+const ROOM_CHANNEL_CAPACITY: usize = 256;
+
+/// A multi-party relay session sharing a single UUID
+pub(crate) struct Room {
+    tx: broadcast::Sender<(ConnId, Message)>,
+    next_id: AtomicU64,
+    members: AtomicU64,
+}
+
+impl Room {
+    fn new() -> Self {
+        let (tx, _rx) = broadcast::channel(ROOM_CHANNEL_CAPACITY);
+        Self {
+            tx,
+            next_id: AtomicU64::new(1),
+            members: AtomicU64::new(0),
+        }
+    }
+
+    fn next_conn_id(&self) -> ConnId {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Rooms, keyed by UUID
+pub type Rooms = Arc<Mutex<HashMap<String, Arc<Room>>>>;
+
+/// Join `uuid`'s room (creating it if this is the first member) and run
+/// this connection's forward/broadcast loop until it disconnects or it
+/// exceeds `limits`.
+///
+/// Counted as one circuit in `metrics` for the duration of the membership,
+/// the same way a 2-party pairing is, so the shutdown drain loop in `main`
+/// waits for room members to finish before the relay exits.
+pub async fn join_room(
+    mut half: ConnectionHalf,
+    uuid: String,
+    rooms: Rooms,
+    limits: Limits,
+    metrics: Arc<Metrics>,
+) -> Result<()> {
+    let room = {
+        let mut guard = rooms.lock().await;
+        Arc::clone(
+            guard
+                .entry(uuid.clone())
+                .or_insert_with(|| Arc::new(Room::new())),
+        )
+    };
+
+    let conn_id = room.next_conn_id();
+    room.members.fetch_add(1, Ordering::Relaxed);
+    info!(
+        "👥 {} ({}) joined room {} as member {}",
+        half.peer_addr, half.role, uuid, conn_id
+    );
+
+    let started_at = Instant::now();
+    metrics.circuit_started();
+
+    let leftover = std::mem::take(&mut half.leftover);
+    let mut parts = FramedParts::new(half.stream, RelayCodec::default());
+    parts.read_buf = leftover;
+    let (mut sink, mut stream) = Framed::from_parts(parts).split();
+
+    let tx = room.tx.clone();
+    let mut rx = tx.subscribe();
+    let _ = tx.send((conn_id, Message::PeerJoined { conn_id }));
+
+    let mut total_bytes_in = 0u64;
+    let mut total_bytes_out = 0u64;
+
+    let pump = async {
+        loop {
+            tokio::select! {
+                msg = stream.next() => {
+                    match msg {
+                        None => {
+                            info!("Room member {} ({}) disconnected from room {}", conn_id, half.peer_addr, uuid);
+                            break;
+                        }
+                        Some(Err(e)) => {
+                            error!("Read error from room member {} ({}): {}", conn_id, half.peer_addr, e);
+                            break;
+                        }
+                        Some(Ok(msg)) => {
+                            total_bytes_in += message_size(&msg);
+                            if total_bytes_in + total_bytes_out > limits.max_bytes {
+                                warn!(
+                                    "Room member {} ({}) exceeded byte cap of {} bytes, disconnecting",
+                                    conn_id, half.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                            // No other members subscribed right now is fine, keep going.
+                            let _ = tx.send((conn_id, msg));
+                        }
+                    }
+                }
+
+                broadcast_msg = rx.recv() => {
+                    match broadcast_msg {
+                        Ok((sender_id, msg)) if sender_id != conn_id => {
+                            total_bytes_out += message_size(&msg);
+                            if let Err(e) = sink.send(msg).await {
+                                error!("Write error to room member {} ({}): {}", conn_id, half.peer_addr, e);
+                                break;
+                            }
+                            if total_bytes_in + total_bytes_out > limits.max_bytes {
+                                warn!(
+                                    "Room member {} ({}) exceeded byte cap of {} bytes, disconnecting",
+                                    conn_id, half.peer_addr, limits.max_bytes
+                                );
+                                break;
+                            }
+                        }
+                        Ok(_) => {
+                            // Our own message, already delivered locally; skip it.
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("Room member {} lagged behind by {} message(s), some were dropped", conn_id, skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            }
+        }
+    };
+
+    if tokio::time::timeout(limits.max_duration, pump).await.is_err() {
+        warn!(
+            "Room member {} ({}) exceeded duration cap of {}s, disconnecting",
+            conn_id, half.peer_addr, limits.max_duration.as_secs()
+        );
+    }
+
+    room.members.fetch_sub(1, Ordering::Relaxed);
+    let _ = tx.send((conn_id, Message::PeerLeft { conn_id }));
+
+    if room.members.load(Ordering::Relaxed) == 0 {
+        rooms.lock().await.remove(&uuid);
+        info!("🧹 Room {} is empty, removing it", uuid);
+    }
+
+    metrics.circuit_closed(started_at.elapsed());
+
+    Ok(())
+}
+
+/// Approximate wire size of a message, for enforcing `Limits::max_bytes`
+/// against room traffic the same way the 2-party proxy counts raw bytes
+fn message_size(msg: &Message) -> u64 {
+    serde_json::to_vec(msg).map(|v| v.len() as u64).unwrap_or(0)
+}